@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use crate::{XtraceEntryRecord, XtraceError, XtraceRecordKind};
+
+/// Which measurement a folded stack's weight column should carry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackWeight {
+    /// Self wall-clock time (excluding children), in microseconds.
+    Time,
+    /// Self memory delta (excluding children), in bytes.
+    Memory,
+}
+
+/// A single reconstructed call, with its entry record and both inclusive and
+/// self time/memory, plus the calls it made while it was on the stack.
+#[derive(Clone, Debug)]
+pub struct CallNode {
+    pub entry: XtraceEntryRecord,
+    pub inclusive_time: f64,
+    pub self_time: f64,
+    pub inclusive_memory: i64,
+    pub self_memory: i64,
+    pub children: Vec<CallNode>,
+}
+
+/// A call not yet closed by a matching function-exit record.
+struct OpenCall {
+    entry: XtraceEntryRecord,
+    children: Vec<CallNode>,
+}
+
+impl OpenCall {
+    fn close(self, exit_time_idx: f64, exit_mem_usage: u32) -> CallNode {
+        let inclusive_time = exit_time_idx - self.entry.time_idx;
+        let inclusive_memory = exit_mem_usage as i64 - self.entry.mem_usage as i64;
+        let children_time: f64 = self.children.iter().map(|c| c.inclusive_time).sum();
+        let children_memory: i64 = self.children.iter().map(|c| c.inclusive_memory).sum();
+        CallNode {
+            entry: self.entry,
+            inclusive_time,
+            self_time: inclusive_time - children_time,
+            inclusive_memory,
+            self_memory: inclusive_memory - children_memory,
+            children: self.children,
+        }
+    }
+}
+
+/// The reconstructed call tree for an xtrace run: a forest of `CallNode`s
+/// (typically a single root, the top-level script), rebuilt from the flat
+/// stream of function-entry/function-exit records by tracking an explicit
+/// stack of open calls.
+#[derive(Clone, Debug, Default)]
+pub struct CallTree {
+    pub roots: Vec<CallNode>,
+}
+
+impl CallTree {
+    /// Builds a `CallTree` from a stream of `XtraceRecordKind`s (e.g. from
+    /// `XtraceLines` or `xtrace_record_stream`). Entries push onto an
+    /// explicit stack; exits pop the matching entry, computing inclusive and
+    /// self time/memory along the way. Exits missing at EOF are unwound using
+    /// the last `time_idx`/`mem_usage` seen, so a truncated trace still
+    /// yields a complete tree.
+    pub fn from_records<I>(records: I) -> Result<CallTree, XtraceError>
+    where
+        I: IntoIterator<Item = Result<XtraceRecordKind, XtraceError>>,
+    {
+        let mut stack: Vec<OpenCall> = Vec::new();
+        let mut roots: Vec<CallNode> = Vec::new();
+        let mut last_time_idx = 0.0_f64;
+        let mut last_mem_usage = 0_u32;
+
+        for record in records {
+            match record? {
+                XtraceRecordKind::Entry(entry) => {
+                    last_time_idx = entry.time_idx;
+                    last_mem_usage = entry.mem_usage;
+                    stack.push(OpenCall {
+                        entry,
+                        children: Vec::new(),
+                    });
+                }
+                XtraceRecordKind::Exit(exit) => {
+                    last_time_idx = exit.time_idx;
+                    last_mem_usage = exit.mem_usage;
+                    let open = match stack.pop() {
+                        Some(open) if open.entry.fn_num == exit.fn_num && open.entry.level == exit.level => {
+                            open
+                        }
+                        Some(_) => return Err(XtraceError::UnbalancedCallStack { fn_num: exit.fn_num }),
+                        None => return Err(XtraceError::UnbalancedCallStack { fn_num: exit.fn_num }),
+                    };
+                    let node = open.close(exit.time_idx, exit.mem_usage);
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(node),
+                        None => roots.push(node),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        while let Some(open) = stack.pop() {
+            let node = open.close(last_time_idx, last_mem_usage);
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => roots.push(node),
+            }
+        }
+
+        Ok(CallTree { roots })
+    }
+
+    /// Renders the tree as a folded-stack string suitable for flame-graph
+    /// tooling: one line per call (leaf or not), each a `;`-joined chain of
+    /// `fn_name`s from root to that call followed by a space and an integer
+    /// weight of time/memory spent in that call *excluding* its children, with
+    /// identical stacks aggregated by summing their weights. Using self
+    /// rather than inclusive weight keeps a parent's own work from being
+    /// double-counted underneath its children's stacks.
+    pub fn fold_stacks(&self, weight: StackWeight) -> String {
+        let mut totals: HashMap<String, u64> = HashMap::new();
+        let mut stack_names: Vec<&str> = Vec::new();
+        for root in &self.roots {
+            fold_node(root, weight, &mut stack_names, &mut totals);
+        }
+
+        let mut lines: Vec<String> = totals
+            .into_iter()
+            .map(|(stack, count)| format!("{stack} {count}"))
+            .collect();
+        lines.sort();
+        lines.join("\n")
+    }
+}
+
+fn fold_node<'a>(
+    node: &'a CallNode,
+    weight: StackWeight,
+    stack_names: &mut Vec<&'a str>,
+    totals: &mut HashMap<String, u64>,
+) {
+    stack_names.push(&node.entry.fn_name);
+
+    let stack = stack_names.join(";");
+    *totals.entry(stack).or_insert(0) += node_self_weight(node, weight);
+
+    for child in &node.children {
+        fold_node(child, weight, stack_names, totals);
+    }
+
+    stack_names.pop();
+}
+
+fn node_self_weight(node: &CallNode, weight: StackWeight) -> u64 {
+    match weight {
+        StackWeight::Time => (node.self_time * 1_000_000.0).round().max(0.0) as u64,
+        StackWeight::Memory => node.self_memory.max(0) as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FnType, RecType, XtraceExitRecord};
+
+    fn entry(level: u32, fn_num: u32, fn_name: &str, time_idx: f64) -> XtraceEntryRecord {
+        XtraceEntryRecord {
+            rec_type: RecType::Entry,
+            level,
+            fn_num,
+            type_tag: 0,
+            time_idx,
+            mem_usage: 0,
+            fn_name: fn_name.to_owned(),
+            fn_type: FnType::User,
+            inc_file_name: String::new(),
+            file_name: String::new(),
+            line_num: 0,
+            arg_num: 0,
+            args: Vec::new(),
+        }
+    }
+
+    fn exit(level: u32, fn_num: u32, time_idx: f64) -> XtraceExitRecord {
+        XtraceExitRecord {
+            level,
+            fn_num,
+            rec_type: RecType::Exit,
+            type_tag: 1,
+            time_idx,
+            mem_usage: 0,
+        }
+    }
+
+    /// `A` runs from `0..10` and calls `B`, which runs from `2..8`, so
+    /// `A.self_time == 4.0` and `B.self_time == 6.0`.
+    #[test]
+    fn fold_stacks_weights_every_node_by_self_time() {
+        let records: Vec<Result<XtraceRecordKind, XtraceError>> = vec![
+            Ok(XtraceRecordKind::Entry(entry(0, 1, "A", 0.0))),
+            Ok(XtraceRecordKind::Entry(entry(1, 2, "B", 2.0))),
+            Ok(XtraceRecordKind::Exit(exit(1, 2, 8.0))),
+            Ok(XtraceRecordKind::Exit(exit(0, 1, 10.0))),
+        ];
+
+        let tree = CallTree::from_records(records).unwrap();
+        let folded = tree.fold_stacks(StackWeight::Time);
+
+        assert_eq!(folded, "A 4000000\nA;B 6000000");
+    }
+}
@@ -0,0 +1,110 @@
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+
+/// The default layout used to parse `TRACE START`/`TRACE END` timestamps when
+/// the caller doesn't supply one of their own.
+pub const DEFAULT_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+/// Describes how a raw, tab-separated xtrace field should be interpreted once
+/// it's been split out of a line, so downstream consumers get typed values
+/// instead of re-parsing strings themselves.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Conversion {
+    /// A byte count, e.g. `mem_usage`.
+    Bytes,
+    /// A plain integer field, e.g. `level` or `fn_num`.
+    Integer,
+    /// A floating point field, e.g. `time_idx`.
+    Float,
+    /// A timestamp using `DEFAULT_TIMESTAMP_FORMAT`.
+    Timestamp,
+    /// A timestamp using a caller-supplied `chrono` format string.
+    TimestampFmt(String),
+}
+
+impl FromStr for Conversion {
+    type Err = std::convert::Infallible;
+
+    /// Maps the conversion names used in config/CLI contexts (`"bytes"`,
+    /// `"int"`/`"integer"`, `"float"`, `"timestamp"`) to a `Conversion`.
+    /// Anything else is treated as a custom `chrono` format string for
+    /// timestamp parsing.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "bytes" => Conversion::Bytes,
+            "int" | "integer" => Conversion::Integer,
+            "float" => Conversion::Float,
+            "timestamp" => Conversion::Timestamp,
+            other => Conversion::TimestampFmt(other.to_owned()),
+        })
+    }
+}
+
+/// The `chrono` format a `TRACE START`/`TRACE END` timestamp should be parsed
+/// with. Narrower than `Conversion` so a `Conversion::Bytes`/`Integer`/`Float`
+/// picked up from config can't be silently handed to timestamp parsing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimestampFormat {
+    /// `DEFAULT_TIMESTAMP_FORMAT`.
+    Default,
+    /// A caller-supplied `chrono` format string.
+    Custom(String),
+}
+
+impl TimestampFormat {
+    fn as_str(&self) -> &str {
+        match self {
+            TimestampFormat::Default => DEFAULT_TIMESTAMP_FORMAT,
+            TimestampFormat::Custom(fmt) => fmt.as_str(),
+        }
+    }
+
+    /// Parses `raw` into a `NaiveDateTime` using this format.
+    pub fn parse(&self, raw: &str) -> Result<NaiveDateTime, chrono::ParseError> {
+        NaiveDateTime::parse_from_str(raw, self.as_str())
+    }
+}
+
+impl FromStr for TimestampFormat {
+    type Err = std::convert::Infallible;
+
+    /// `"timestamp"` maps to `DEFAULT_TIMESTAMP_FORMAT`; anything else is
+    /// treated as a custom `chrono` format string.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "timestamp" => TimestampFormat::Default,
+            other => TimestampFormat::Custom(other.to_owned()),
+        })
+    }
+}
+
+/// Only `Conversion::Timestamp`/`TimestampFmt` describe a timestamp layout;
+/// `Bytes`/`Integer`/`Float` have no format to offer.
+impl TryFrom<&Conversion> for TimestampFormat {
+    type Error = ();
+
+    fn try_from(conversion: &Conversion) -> Result<Self, Self::Error> {
+        match conversion {
+            Conversion::Timestamp => Ok(TimestampFormat::Default),
+            Conversion::TimestampFmt(fmt) => Ok(TimestampFormat::Custom(fmt.clone())),
+            Conversion::Bytes | Conversion::Integer | Conversion::Float => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_timestamp_conversions_cannot_become_a_timestamp_format() {
+        assert!(TimestampFormat::try_from(&Conversion::Bytes).is_err());
+        assert!(TimestampFormat::try_from(&Conversion::Integer).is_err());
+        assert!(TimestampFormat::try_from(&Conversion::Float).is_err());
+        assert_eq!(
+            TimestampFormat::try_from(&Conversion::Timestamp).unwrap(),
+            TimestampFormat::Default
+        );
+    }
+}
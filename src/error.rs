@@ -0,0 +1,39 @@
+use thiserror::Error;
+
+use crate::RecType;
+
+/// Everything that can go wrong while turning raw xtrace lines into records.
+#[derive(Debug, Error)]
+pub enum XtraceError {
+    #[error("failed to read trace file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("unsupported trace file format: {found}")]
+    UnsupportedFormat { found: String },
+    #[error("malformed {kind:?} record on line {line_number}: {source}")]
+    MalformedRecord {
+        line_number: u32,
+        kind: RecType,
+        source: Box<XtraceError>,
+    },
+    #[error("record is missing a required field")]
+    MissingField,
+    #[error("failed to parse integer field")]
+    IntParse,
+    #[error("failed to parse floating point field")]
+    FloatParse,
+    #[error("unknown function type")]
+    UnknownFnType,
+    #[error("function exit for fn_num {fn_num} does not match the currently open call")]
+    UnbalancedCallStack { fn_num: u32 },
+}
+
+/// Controls how `parse_xtrace_file` behaves when it encounters a malformed
+/// line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Stop at the first error and return it.
+    #[default]
+    Strict,
+    /// Collect errors into `XtraceFileRecord::errors` and keep going.
+    Lenient,
+}
@@ -8,7 +8,26 @@ use std::path::Path;
 use lazy_static::lazy_static;
 use regex::{Regex, RegexSet};
 
-static SUPPORTED_FILE_FORMATS: &[&str] = &["4"];
+mod call_tree;
+mod conversion;
+mod error;
+mod stream;
+
+pub use call_tree::{CallNode, CallTree, StackWeight};
+pub use conversion::{Conversion, TimestampFormat, DEFAULT_TIMESTAMP_FORMAT};
+pub use error::{ParseMode, XtraceError};
+pub use stream::{XtraceLines, XtraceRecordKind};
+#[cfg(feature = "async")]
+pub use stream::xtrace_record_stream;
+
+/// Trace file formats this parser accepts by default. Only `"4"` (the
+/// standard tab-separated format) is verified to match the field layout
+/// this parser expects. Xdebug's "computed" format may use a different
+/// layout depending on version; rather than guess, callers who know their
+/// trace's format matches can opt in explicitly via
+/// `XtraceFmtRecord::new_with_formats`.
+pub static DEFAULT_SUPPORTED_FILE_FORMATS: &[&str] = &["4"];
+static SUPPORTED_FILE_FORMATS: &[&str] = DEFAULT_SUPPORTED_FILE_FORMATS;
 lazy_static! {
         static ref RE_SET: regex::RegexSet = RegexSet::new([
             LineRegex::Version.regex_str(),
@@ -18,32 +37,40 @@ lazy_static! {
             LineRegex::FunctionExit.regex_str(),
             LineRegex::Penultimate.regex_str(),
             LineRegex::End.regex_str(),
+            LineRegex::Return.regex_str(),
         ])
         .unwrap();
     }
 #[derive(Clone, Debug)]
-enum RecType {
+pub enum RecType {
     Entry,
     Exit,
+    Return,
     Format,
     StartTime,
+    EndTime,
     Version,
 }
 
-trait XtraceRecord {
-    fn new(line: &str) -> Self;
+trait XtraceRecord: Sized {
+    fn new(line: &str) -> Result<Self, XtraceError>;
 }
 
 trait XtraceFn {}
 
 #[allow(unused)]
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct XtraceFileRecord {
     pub id: uuid::Uuid,
     pub start: Option<XtraceStartTimeRecord>,
+    pub end: Option<XtraceEndTimeRecord>,
     pub format: Option<XtraceFmtRecord>,
     pub version: Option<XtraceVersionRecord>,
     pub fn_records: Vec<XtraceFnRecord>,
+    /// Errors collected while parsing in `ParseMode::Lenient`. Always empty
+    /// when parsing in `ParseMode::Strict`, since the first error aborts
+    /// parsing instead.
+    pub errors: Vec<XtraceError>,
 }
 
 impl XtraceFileRecord {
@@ -73,22 +100,22 @@ pub struct XtraceFnRecord {
     pub fn_num: u32,
     pub entry_record: Option<XtraceEntryRecord>,
     pub exit_record: Option<XtraceExitRecord>,
-    //return_record: Option<XtraceReturnRecord>,
+    pub return_record: Option<XtraceReturnRecord>,
 }
 
 impl XtraceRecord for XtraceVersionRecord {
-    fn new(line: &str) -> Self {
+    fn new(line: &str) -> Result<Self, XtraceError> {
         let re = Regex::new(LineRegex::Version.regex_str()).unwrap();
-        let cap = re.captures(line).unwrap();
+        let cap = re.captures(line).ok_or(XtraceError::MissingField)?;
         let version = cap
             .name("version")
-            .expect("version number not found")
+            .ok_or(XtraceError::MissingField)?
             .as_str()
             .to_owned();
-        XtraceVersionRecord {
+        Ok(XtraceVersionRecord {
             version,
             rec_type: RecType::Version,
-        }
+        })
     }
 }
 
@@ -100,14 +127,18 @@ pub struct XtraceVersionRecord {
 }
 
 impl XtraceRecord for XtraceStartTimeRecord {
-    fn new(line: &str) -> Self {
+    fn new(line: &str) -> Result<Self, XtraceError> {
         let re = Regex::new(LineRegex::Start.regex_str()).unwrap();
-        let cap = re.captures(line).ok_or("oops").unwrap();
+        let cap = re.captures(line).ok_or(XtraceError::MissingField)?;
 
-        XtraceStartTimeRecord {
-            start_time: cap.name("start").unwrap().as_str().to_owned(),
+        Ok(XtraceStartTimeRecord {
+            start_time: cap
+                .name("start")
+                .ok_or(XtraceError::MissingField)?
+                .as_str()
+                .to_owned(),
             rec_type: RecType::StartTime,
-        }
+        })
     }
 }
 
@@ -118,23 +149,73 @@ pub struct XtraceStartTimeRecord {
     rec_type: RecType,
 }
 
+impl XtraceStartTimeRecord {
+    /// Parses `start_time` into a `chrono::NaiveDateTime` using `format`
+    /// (`DEFAULT_TIMESTAMP_FORMAT` unless a custom one was supplied).
+    pub fn parsed_start_time(
+        &self,
+        format: &TimestampFormat,
+    ) -> Result<chrono::NaiveDateTime, chrono::ParseError> {
+        format.parse(&self.start_time)
+    }
+}
+
+impl XtraceRecord for XtraceEndTimeRecord {
+    fn new(line: &str) -> Result<Self, XtraceError> {
+        let re = Regex::new(LineRegex::End.regex_str()).unwrap();
+        let cap = re.captures(line).ok_or(XtraceError::MissingField)?;
+
+        Ok(XtraceEndTimeRecord {
+            end_time: cap
+                .name("end")
+                .ok_or(XtraceError::MissingField)?
+                .as_str()
+                .to_owned(),
+            rec_type: RecType::EndTime,
+        })
+    }
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct XtraceEndTimeRecord {
+    pub end_time: String,
+    rec_type: RecType,
+}
+
+impl XtraceEndTimeRecord {
+    /// Parses `end_time` into a `chrono::NaiveDateTime` using `format`
+    /// (`DEFAULT_TIMESTAMP_FORMAT` unless a custom one was supplied).
+    pub fn parsed_end_time(
+        &self,
+        format: &TimestampFormat,
+    ) -> Result<chrono::NaiveDateTime, chrono::ParseError> {
+        format.parse(&self.end_time)
+    }
+}
+
 impl XtraceRecord for XtraceFmtRecord {
-    fn new(line: &str) -> Self {
+    fn new(line: &str) -> Result<Self, XtraceError> {
+        Self::new_with_formats(line, SUPPORTED_FILE_FORMATS)
+    }
+}
+
+impl XtraceFmtRecord {
+    /// Parses a `File format: N` line, accepting any format in `supported`
+    /// instead of the crate's `DEFAULT_SUPPORTED_FILE_FORMATS`.
+    pub fn new_with_formats(line: &str, supported: &[&str]) -> Result<Self, XtraceError> {
         let re = Regex::new(LineRegex::Format.regex_str()).unwrap();
-        let cap = re.captures(line).ok_or("oops").unwrap();
-        let format = cap
-            .name("format")
-            .expect("version number not found")
-            .as_str();
-        if SUPPORTED_FILE_FORMATS.contains(&format) {
-            XtraceFmtRecord {
-                format: format
-                    .parse::<u32>()
-                    .expect("Unable to parse format number into an integer"),
+        let cap = re.captures(line).ok_or(XtraceError::MissingField)?;
+        let format = cap.name("format").ok_or(XtraceError::MissingField)?.as_str();
+        if supported.contains(&format) {
+            Ok(XtraceFmtRecord {
+                format: format.parse::<u32>().map_err(|_| XtraceError::IntParse)?,
                 rec_type: RecType::Format,
-            }
+            })
         } else {
-            panic!("Unsupported version: {}", format);
+            Err(XtraceError::UnsupportedFormat {
+                found: format.to_owned(),
+            })
         }
     }
 }
@@ -153,35 +234,48 @@ pub enum FnType {
 }
 
 impl FnType {
-    fn from(num: u8) -> FnType {
+    fn from(num: u8) -> Result<FnType, XtraceError> {
         match num {
-            0 => FnType::Internal,
-            1 => FnType::User,
-            _ => panic!("Found unknown function type: {num}"),
+            0 => Ok(FnType::Internal),
+            1 => Ok(FnType::User),
+            _ => Err(XtraceError::UnknownFnType),
         }
     }
 }
 
 impl XtraceFn for XtraceEntryRecord {}
 impl XtraceRecord for XtraceEntryRecord {
-    fn new(line: &str) -> Self {
+    fn new(line: &str) -> Result<Self, XtraceError> {
         let this_line = line.trim();
         let mut fields: VecDeque<&str> = this_line.split("\t").collect();
-        return XtraceEntryRecord {
+        let mut next = || fields.pop_front().ok_or(XtraceError::MissingField);
+        let level = next()?.parse::<u32>().map_err(|_| XtraceError::IntParse)?;
+        let fn_num = next()?.parse::<u32>().map_err(|_| XtraceError::IntParse)?;
+        let type_tag = next()?.parse::<u8>().map_err(|_| XtraceError::IntParse)?;
+        let time_idx = next()?.parse::<f64>().map_err(|_| XtraceError::FloatParse)?;
+        let mem_usage = next()?.parse::<u32>().map_err(|_| XtraceError::IntParse)?;
+        let fn_name = next()?.to_owned();
+        let fn_type = FnType::from(next()?.parse::<u8>().map_err(|_| XtraceError::IntParse)?)?;
+        let inc_file_name = next()?.to_owned();
+        let file_name = next()?.to_owned();
+        let line_num = next()?.parse::<u32>().map_err(|_| XtraceError::IntParse)?;
+        let arg_num = next()?.parse::<u32>().map_err(|_| XtraceError::IntParse)?;
+        let args = fields.iter().map(|f| f.to_string()).collect();
+        Ok(XtraceEntryRecord {
             rec_type: RecType::Entry,
-            level: fields.pop_front().unwrap().parse::<u32>().unwrap(),
-            fn_num: fields.pop_front().unwrap().parse::<u32>().unwrap(),
-            type_tag: fields.pop_front().unwrap().parse::<u8>().unwrap(),
-            time_idx: fields.pop_front().unwrap().parse::<f64>().unwrap(),
-            mem_usage: fields.pop_front().unwrap().parse::<u32>().unwrap(),
-            fn_name: fields.pop_front().unwrap().to_owned(),
-            fn_type: FnType::from(fields.pop_front().unwrap().parse::<u8>().unwrap()),
-            inc_file_name: fields.pop_front().unwrap().to_owned(),
-            file_name: fields.pop_front().unwrap().to_owned(),
-            line_num: fields.pop_front().unwrap().parse::<u32>().unwrap(),
-            arg_num: fields.pop_front().unwrap().parse::<u32>().unwrap(),
-            args: fields.iter().map(|f| f.to_string()).collect(),
-        };
+            level,
+            fn_num,
+            type_tag,
+            time_idx,
+            mem_usage,
+            fn_name,
+            fn_type,
+            inc_file_name,
+            file_name,
+            line_num,
+            arg_num,
+            args,
+        })
     }
 }
 
@@ -206,17 +300,18 @@ pub struct XtraceEntryRecord {
 
 impl XtraceFn for XtraceExitRecord {}
 impl XtraceRecord for XtraceExitRecord {
-    fn new(line: &str) -> Self {
+    fn new(line: &str) -> Result<Self, XtraceError> {
         let this_line = line.trim();
         let mut fields: VecDeque<&str> = this_line.split("\t").collect();
-        XtraceExitRecord {
+        let mut next = || fields.pop_front().ok_or(XtraceError::MissingField);
+        Ok(XtraceExitRecord {
             rec_type: RecType::Exit,
-            level: fields.pop_front().unwrap().parse::<u32>().unwrap(),
-            fn_num: fields.pop_front().unwrap().parse::<u32>().unwrap(),
-            type_tag: fields.pop_front().unwrap().parse::<u8>().unwrap(),
-            time_idx: fields.pop_front().unwrap().parse::<f64>().unwrap(),
-            mem_usage: fields.pop_front().unwrap().parse::<u32>().unwrap(),
-        }
+            level: next()?.parse::<u32>().map_err(|_| XtraceError::IntParse)?,
+            fn_num: next()?.parse::<u32>().map_err(|_| XtraceError::IntParse)?,
+            type_tag: next()?.parse::<u8>().map_err(|_| XtraceError::IntParse)?,
+            time_idx: next()?.parse::<f64>().map_err(|_| XtraceError::FloatParse)?,
+            mem_usage: next()?.parse::<u32>().map_err(|_| XtraceError::IntParse)?,
+        })
     }
 }
 #[allow(unused)]
@@ -230,6 +325,33 @@ pub struct XtraceExitRecord {
     pub mem_usage: u32,
 }
 
+impl XtraceRecord for XtraceReturnRecord {
+    fn new(line: &str) -> Result<Self, XtraceError> {
+        let this_line = line.trim();
+        let mut fields: VecDeque<&str> = this_line.split("\t").collect();
+        let mut next = || fields.pop_front().ok_or(XtraceError::MissingField);
+        let level = next()?.parse::<u32>().map_err(|_| XtraceError::IntParse)?;
+        let fn_num = next()?.parse::<u32>().map_err(|_| XtraceError::IntParse)?;
+        next()?; // the literal "R" type tag, already used to route here
+        let ret_val = next()?.to_owned();
+        Ok(XtraceReturnRecord {
+            rec_type: RecType::Return,
+            level,
+            fn_num,
+            ret_val,
+        })
+    }
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct XtraceReturnRecord {
+    rec_type: RecType,
+    pub level: u32,
+    pub fn_num: u32,
+    pub ret_val: String,
+}
+
 enum LineRegex {
     Version,
     Format,
@@ -238,6 +360,7 @@ enum LineRegex {
     FunctionExit,
     End,
     Penultimate,
+    Return,
 }
 
 impl LineRegex {
@@ -258,50 +381,132 @@ impl LineRegex {
             LineRegex::End => {
                 r"^TRACE END\s+\[(?P<end>\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}.\d+)\]"
             }
+            LineRegex::Return => r"^(\d+)\t(\d+)\tR\t(.*)",
         }
     }
 }
 
+/// Parses a single raw xtrace line into the record kind its leading regex
+/// matched, or `Ok(None)` for lines that carry no record of their own
+/// (unmatched lines, and the indented time/memory summary line that
+/// precedes `TRACE END`).
+/// Shared by the eager `parse_xtrace_file` and the incremental readers in
+/// the `stream` module so they can't drift apart.
+pub(crate) fn classify_line(
+    line: &str,
+    line_number: u32,
+) -> Result<Option<XtraceRecordKind>, XtraceError> {
+    let matches: Vec<_> = RE_SET.matches(line).into_iter().collect();
+    let idx = match matches.first() {
+        Some(idx) => *idx,
+        None => {
+            eprintln!("No matches for line: {line}");
+            return Ok(None);
+        }
+    };
+    let kind = match idx {
+        0 => XtraceRecordKind::Version(XtraceVersionRecord::new(line).map_err(|e| {
+            XtraceError::MalformedRecord {
+                line_number,
+                kind: RecType::Version,
+                source: Box::new(e),
+            }
+        })?),
+        1 => XtraceRecordKind::Format(XtraceFmtRecord::new(line)?),
+        2 => XtraceRecordKind::Start(XtraceStartTimeRecord::new(line).map_err(|e| {
+            XtraceError::MalformedRecord {
+                line_number,
+                kind: RecType::StartTime,
+                source: Box::new(e),
+            }
+        })?),
+        3 => XtraceRecordKind::Entry(XtraceEntryRecord::new(line).map_err(|e| {
+            XtraceError::MalformedRecord {
+                line_number,
+                kind: RecType::Entry,
+                source: Box::new(e),
+            }
+        })?),
+        4 => XtraceRecordKind::Exit(XtraceExitRecord::new(line).map_err(|e| {
+            XtraceError::MalformedRecord {
+                line_number,
+                kind: RecType::Exit,
+                source: Box::new(e),
+            }
+        })?),
+        5 => return Ok(None),
+        6 => XtraceRecordKind::End(XtraceEndTimeRecord::new(line).map_err(|e| {
+            XtraceError::MalformedRecord {
+                line_number,
+                kind: RecType::EndTime,
+                source: Box::new(e),
+            }
+        })?),
+        7 => XtraceRecordKind::Return(XtraceReturnRecord::new(line).map_err(|e| {
+            XtraceError::MalformedRecord {
+                line_number,
+                kind: RecType::Return,
+                source: Box::new(e),
+            }
+        })?),
+        _ => unreachable!("RE_SET only contains the match arms handled above"),
+    };
+    Ok(Some(kind))
+}
+
 fn process_line(
     run: &mut XtraceFileRecord,
     entry_cache: &mut HashMap<u32, XtraceEntryRecord>,
-    line: &String,
-) {
-    let matches: Vec<_> = RE_SET.matches(line.as_str()).into_iter().collect();
-    if matches.is_empty() {
-        eprintln!("No matches for line: {line}");
-        return;
-    }
-    let idx = matches.first().unwrap();
-    match idx {
-        0 => run.version = Some(XtraceVersionRecord::new(line)),
-        1 => run.format = Some(XtraceFmtRecord::new(line)),
-        2 => run.start = Some(XtraceStartTimeRecord::new(line)),
-        3 => {
-            let record = XtraceEntryRecord::new(line);
+    return_cache: &mut HashMap<u32, XtraceReturnRecord>,
+    line: &str,
+    line_number: u32,
+) -> Result<(), XtraceError> {
+    match classify_line(line, line_number)? {
+        Some(XtraceRecordKind::Version(record)) => run.version = Some(record),
+        Some(XtraceRecordKind::Format(record)) => run.format = Some(record),
+        Some(XtraceRecordKind::Start(record)) => run.start = Some(record),
+        Some(XtraceRecordKind::End(record)) => run.end = Some(record),
+        Some(XtraceRecordKind::Entry(record)) => {
             entry_cache.insert(record.fn_num, record);
         }
-        4 => {
-            let exit_record = XtraceExitRecord::new(line);
+        Some(XtraceRecordKind::Return(record)) => {
+            // `xdebug.collect_return` may emit the `R` line either before or
+            // after the matching exit line, so a return that arrives after
+            // its `fn_record` has already been emitted has to patch it in
+            // place instead of being dropped on the floor.
+            match run
+                .fn_records
+                .iter_mut()
+                .rev()
+                .find(|f| f.fn_num == record.fn_num && f.return_record.is_none())
+            {
+                Some(fn_record) => fn_record.return_record = Some(record),
+                None => {
+                    return_cache.insert(record.fn_num, record);
+                }
+            }
+        }
+        Some(XtraceRecordKind::Exit(exit_record)) => {
             if let Some(entry_record) = entry_cache.get(&exit_record.fn_num) {
                 let fn_record = XtraceFnRecord {
                     fn_num: exit_record.fn_num,
                     entry_record: Some(entry_record.to_owned()),
+                    return_record: return_cache.remove(&exit_record.fn_num),
                     exit_record: Some(exit_record),
                 };
                 run.add_fn_record(fn_record);
             }
         }
-        5 => {}
-        6 => {}
-        _ => todo!(),
-    };
+        None => {}
+    }
+    Ok(())
 }
 
 pub fn parse_xtrace_file(
     id: uuid::Uuid,
     file: &Path,
-) -> Result<XtraceFileRecord, std::io::Error> {
+    mode: ParseMode,
+) -> Result<XtraceFileRecord, XtraceError> {
     let xtrace_file = File::open(file)?;
     let mut reader = BufReader::new(xtrace_file);
     //let mut line = String::new();
@@ -310,10 +515,13 @@ pub fn parse_xtrace_file(
         id,
         format: None,
         start: None,
+        end: None,
         version: None,
         fn_records: Vec::new(),
+        errors: Vec::new(),
     };
     let mut entry_cache: HashMap<u32, XtraceEntryRecord> = HashMap::new();
+    let mut return_cache: HashMap<u32, XtraceReturnRecord> = HashMap::new();
     let mut line_number: u32 = 1;
     loop {
         //let result = reader.read_line(&mut line);
@@ -324,14 +532,23 @@ pub fn parse_xtrace_file(
                     return Ok(file_run);
                 }
                 //println!("Processing line {line_number}: {line}");
-                if line.len() == 1 { // likely just a newline
+                if line.len() == 1 {
+                    // likely just a newline
                     continue;
                 }
-                process_line(
+                let result = process_line(
                     &mut file_run,
                     &mut entry_cache,
+                    &mut return_cache,
                     &String::from_utf8_lossy(line.as_slice()).to_string(),
+                    line_number,
                 );
+                if let Err(e) = result {
+                    match mode {
+                        ParseMode::Strict => return Err(e),
+                        ParseMode::Lenient => file_run.errors.push(e),
+                    }
+                }
             }
             Err(e) => {
                 eprintln!("Error reading line #{line_number}: {e}");
@@ -343,21 +560,112 @@ pub fn parse_xtrace_file(
     }
 }
 
-// Not yet implemented
-/*    struct XtraceReturnRecord {
-    level: u32,
-    fn_num: u32,
-    rec_type: RecType,
-    ret_val: u32, // Need to confirm this type. I have yet to see an example to work from and the docs aren't specific.
-}*/
+/// Reconstructs the call tree for an xtrace file, reading it incrementally
+/// via `XtraceLines` rather than accumulating an `XtraceFileRecord`.
+pub fn parse_call_tree(file: &Path) -> Result<CallTree, XtraceError> {
+    let xtrace_file = File::open(file)?;
+    CallTree::from_records(XtraceLines::new(xtrace_file))
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn parsed_start_time_uses_default_format() {
+        let record = XtraceStartTimeRecord::new("TRACE START [2024-01-02 03:04:05.678900]\n").unwrap();
+        let parsed = record.parsed_start_time(&TimestampFormat::Default).unwrap();
+        assert_eq!(parsed.to_string(), "2024-01-02 03:04:05.678900");
+    }
+
+    #[test]
+    fn parsed_end_time_honors_custom_format() {
+        let record = XtraceEndTimeRecord::new("TRACE END   [2024-01-02 03:04:05.678900]\n").unwrap();
+        let parsed = record
+            .parsed_end_time(&TimestampFormat::Custom("%Y-%m-%d %H:%M:%S%.f".to_owned()))
+            .unwrap();
+        assert_eq!(parsed.to_string(), "2024-01-02 03:04:05.678900");
+    }
+
+    fn write_temp_trace(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}_{}.xt", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn lenient_mode_collects_errors_and_keeps_parsing() {
+        let path = write_temp_trace(
+            "xtrace_lenient_test",
+            "Version: 3.2.1\n99999999999999999999\t1\t0\t0.000100\t1024\tmain\t1\t/tmp/test.php\t/tmp/test.php\t1\t0\t\n",
+        );
+
+        let result = parse_xtrace_file(uuid::Uuid::nil(), &path, ParseMode::Lenient).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert!(result.version.is_some());
+        assert_eq!(result.errors.len(), 1);
+        assert!(matches!(
+            result.errors[0],
+            XtraceError::MalformedRecord {
+                kind: RecType::Entry,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn strict_mode_stops_at_first_malformed_line() {
+        let path = write_temp_trace(
+            "xtrace_strict_test",
+            "99999999999999999999\t1\t0\t0.000100\t1024\tmain\t1\t/tmp/test.php\t/tmp/test.php\t1\t0\t\nVersion: 3.2.1\n",
+        );
+
+        let result = parse_xtrace_file(uuid::Uuid::nil(), &path, ParseMode::Strict);
+        std::fs::remove_file(&path).ok();
+
+        assert!(matches!(
+            result,
+            Err(XtraceError::MalformedRecord {
+                kind: RecType::Entry,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn return_record_round_trips_into_the_matching_fn_record() {
+        let path = write_temp_trace(
+            "xtrace_return_test",
+            "0\t1\t0\t0.000100\t1024\tstrlen\t1\t/tmp/test.php\t/tmp/test.php\t1\t0\t\n\
+             0\t1\tR\t5\n\
+             0\t1\t1\t0.000200\t1040\n",
+        );
+
+        let result = parse_xtrace_file(uuid::Uuid::nil(), &path, ParseMode::Strict).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(result.fn_records.len(), 1);
+        let return_record = result.fn_records[0].return_record.as_ref().unwrap();
+        assert_eq!(return_record.fn_num, 1);
+        assert_eq!(return_record.ret_val, "5");
+    }
+
+    #[test]
+    fn late_return_patches_the_already_emitted_fn_record() {
+        let path = write_temp_trace(
+            "xtrace_late_return_test",
+            "0\t1\t0\t0.000100\t1024\tstrlen\t1\t/tmp/test.php\t/tmp/test.php\t1\t0\t\n\
+             0\t1\t1\t0.000200\t1040\n\
+             0\t1\tR\t5\n",
+        );
+
+        let result = parse_xtrace_file(uuid::Uuid::nil(), &path, ParseMode::Strict).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(result.fn_records.len(), 1);
+        let return_record = result.fn_records[0].return_record.as_ref().unwrap();
+        assert_eq!(return_record.fn_num, 1);
+        assert_eq!(return_record.ret_val, "5");
     }
 }
@@ -0,0 +1,163 @@
+use std::io::{BufRead, BufReader, Read};
+
+use crate::{
+    classify_line, XtraceEndTimeRecord, XtraceEntryRecord, XtraceError, XtraceExitRecord,
+    XtraceFmtRecord, XtraceReturnRecord, XtraceStartTimeRecord, XtraceVersionRecord,
+};
+
+/// A single parsed xtrace line, unifying every record kind the parser
+/// understands so callers can process a trace incrementally instead of
+/// waiting for the whole file to be read into an `XtraceFileRecord`.
+#[derive(Clone, Debug)]
+pub enum XtraceRecordKind {
+    Version(XtraceVersionRecord),
+    Format(XtraceFmtRecord),
+    Start(XtraceStartTimeRecord),
+    End(XtraceEndTimeRecord),
+    Entry(XtraceEntryRecord),
+    Exit(XtraceExitRecord),
+    Return(XtraceReturnRecord),
+}
+
+/// A synchronous, incremental adapter over any `Read` that yields one
+/// `XtraceRecordKind` per call to `next`, bounding memory usage instead of
+/// accumulating every record into an `XtraceFileRecord::fn_records` vector.
+pub struct XtraceLines<R> {
+    reader: BufReader<R>,
+    buf: Vec<u8>,
+    line_number: u32,
+}
+
+impl<R: Read> XtraceLines<R> {
+    pub fn new(reader: R) -> Self {
+        XtraceLines {
+            reader: BufReader::new(reader),
+            buf: Vec::new(),
+            line_number: 0,
+        }
+    }
+}
+
+impl<R: Read> Iterator for XtraceLines<R> {
+    type Item = Result<XtraceRecordKind, XtraceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_until(0xA, &mut self.buf) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    self.line_number += 1;
+                    if self.buf.len() <= 1 {
+                        // likely just a newline
+                        continue;
+                    }
+                    let line = String::from_utf8_lossy(&self.buf).to_string();
+                    match classify_line(&line, self.line_number) {
+                        Ok(Some(kind)) => return Some(Ok(kind)),
+                        Ok(None) => continue,
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                Err(e) => return Some(Err(XtraceError::Io(e))),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+mod async_impl {
+    use async_stream::try_stream;
+    use futures_core::Stream;
+    use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+    use super::XtraceRecordKind;
+    use crate::{classify_line, XtraceError};
+
+    /// The async counterpart to `XtraceLines`: streams `XtraceRecordKind`s
+    /// out of an `AsyncBufRead` as they're read, so a multi-gigabyte trace
+    /// never has to be held in memory all at once.
+    pub fn xtrace_record_stream<R>(
+        mut reader: R,
+    ) -> impl Stream<Item = Result<XtraceRecordKind, XtraceError>>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        try_stream! {
+            let mut buf: Vec<u8> = Vec::new();
+            let mut line_number: u32 = 0;
+            loop {
+                buf.clear();
+                let size = reader.read_until(0xA, &mut buf).await.map_err(XtraceError::Io)?;
+                if size == 0 {
+                    break;
+                }
+                line_number += 1;
+                if buf.len() <= 1 {
+                    // likely just a newline
+                    continue;
+                }
+                let line = String::from_utf8_lossy(&buf).to_string();
+                if let Some(kind) = classify_line(&line, line_number)? {
+                    yield kind;
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::io::Cursor;
+
+        use futures_util::StreamExt;
+        use tokio::io::BufReader;
+
+        use super::*;
+
+        #[tokio::test]
+        async fn yields_one_record_kind_per_line_and_skips_blanks() {
+            let trace = "Version: 2.0.4\n\
+                 \n\
+                 0\t0\t0\t0.0\t393216\t{main}\t1\t\t/var/www/index.php\t0\t0\n\
+                 0\t0\t1\t0.1\t400000\n";
+            let reader = BufReader::new(Cursor::new(trace.as_bytes()));
+            let stream = xtrace_record_stream(reader);
+            futures_util::pin_mut!(stream);
+
+            let mut kinds = Vec::new();
+            while let Some(result) = stream.next().await {
+                kinds.push(result.unwrap());
+            }
+
+            assert_eq!(kinds.len(), 3);
+            assert!(matches!(kinds[0], XtraceRecordKind::Version(_)));
+            assert!(matches!(kinds[1], XtraceRecordKind::Entry(_)));
+            assert!(matches!(kinds[2], XtraceRecordKind::Exit(_)));
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+pub use async_impl::xtrace_record_stream;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn yields_one_record_kind_per_line_and_skips_blanks() {
+        let trace = "Version: 2.0.4\n\
+             \n\
+             0\t0\t0\t0.0\t393216\t{main}\t1\t\t/var/www/index.php\t0\t0\n\
+             0\t0\t1\t0.1\t400000\n";
+        let lines = XtraceLines::new(Cursor::new(trace.as_bytes()));
+        let kinds: Vec<_> = lines.map(|r| r.unwrap()).collect();
+
+        assert_eq!(kinds.len(), 3);
+        assert!(matches!(kinds[0], XtraceRecordKind::Version(_)));
+        assert!(matches!(kinds[1], XtraceRecordKind::Entry(_)));
+        assert!(matches!(kinds[2], XtraceRecordKind::Exit(_)));
+    }
+}